@@ -5,6 +5,8 @@ extern crate iron;
 extern crate logger;
 #[macro_use] extern crate mime;
 extern crate num;
+extern crate rand;
+extern crate rayon;
 extern crate router;
 extern crate urlencoded;
 
@@ -14,24 +16,111 @@ use iron::prelude::*;
 use iron::status;
 use logger::Logger;
 use num::Complex;
+use rand::Rng;
+use rayon::prelude::*;
 use router::Router;
 use std::str::FromStr;
 use urlencoded::UrlEncodedQuery;
 
-/// Approximated Mandelbrot containment test
+/// Selects which fractal iteration `approx_mandelbrot_test` should evaluate
+///
+/// `Mandelbrot` is the classic `z = z^2 + c` recurrence, `MandelbrotN` generalizes
+/// it to an arbitrary integer power `z = z^p + c`, and `BurningShip` takes the
+/// absolute value of `z`'s real and imaginary parts before squaring, which folds
+/// the set into the "Burning Ship" fractal
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum FractalKind {
+    #[default]
+    Mandelbrot,
+    MandelbrotN(i32),
+    BurningShip,
+}
+
+/// Parse a `FractalKind` from a query parameter value
+/// `mandelbrot` and `burning_ship` select the fixed fractals, while
+/// `mandelbrot_n:<p>` selects `MandelbrotN` with power `p`
+impl FromStr for FractalKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mandelbrot" => Ok(FractalKind::Mandelbrot),
+            "burning_ship" => Ok(FractalKind::BurningShip),
+            _ if s.starts_with("mandelbrot_n:") => {
+                match i32::from_str(&s["mandelbrot_n:".len()..]) {
+                    Ok(p) => Ok(FractalKind::MandelbrotN(p)),
+                    Err(_) => Err(())
+                }
+            }
+            _ => Err(())
+        }
+    }
+}
+
+#[test]
+fn test_fractal_kind_from_str() {
+    assert_eq!(FractalKind::from_str("mandelbrot"), Ok(FractalKind::Mandelbrot));
+    assert_eq!(FractalKind::from_str("burning_ship"), Ok(FractalKind::BurningShip));
+    assert_eq!(FractalKind::from_str("mandelbrot_n:3"), Ok(FractalKind::MandelbrotN(3)));
+    assert_eq!(FractalKind::from_str("mandelbrot_n:"), Err(()));
+    assert_eq!(FractalKind::from_str("nonsense"), Err(()));
+}
+
+/// Apply one iteration step of `kind`'s recurrence to `z`
+fn fractal_step(kind: FractalKind, z: Complex<f64>, c: Complex<f64>) -> Complex<f64> {
+    match kind {
+        FractalKind::Mandelbrot => z * z + c,
+        FractalKind::MandelbrotN(p) => z.powi(p) + c,
+        FractalKind::BurningShip => {
+            let folded = Complex { re: z.re.abs(), im: z.im.abs() };
+            folded * folded + c
+        }
+    }
+}
+
+#[test]
+fn test_fractal_step() {
+    let z = Complex { re: -1.0, im: 2.0 };
+    let c = Complex { re: 0.0, im: 0.0 };
+    assert_eq!(fractal_step(FractalKind::Mandelbrot, z, c), Complex { re: -3.0, im: -4.0 });
+    assert_eq!(fractal_step(FractalKind::MandelbrotN(3), z, c), Complex { re: 11.0, im: -2.0 });
+    assert_eq!(fractal_step(FractalKind::BurningShip, z, c), Complex { re: -3.0, im: 4.0 });
+}
+
+/// Smooth, fractional iteration count past escape, used to avoid the visible
+/// banding that an integer escape count produces
+///
+/// `i` is the integer iteration at which `z` first escaped; `z` should already
+/// have been carried a couple of iterations further, since the extra steps
+/// sharpen the estimate
+fn smooth_iteration_count(i: u32, z: Complex<f64>) -> f64 {
+    i as f64 + 1.0 - (z.norm_sqr().ln() / 2.0).ln() / 2f64.ln()
+}
+
+#[test]
+fn test_smooth_iteration_count() {
+    // |z| = e^2, so log(log(|z|)) / log(2) == 1 and mu reduces to i
+    let z = Complex { re: std::f64::consts::E.powi(2), im: 0.0 };
+    assert!((smooth_iteration_count(5, z) - 5.0).abs() < 1e-9);
+}
+
+/// Escape-time test for the fractal selected by `kind`
 /// `limit` caps the number of iterations to try to
-/// evaluate if Mandelbrot contains `c`
+/// evaluate if the set contains `c`
 ///
-/// If `c` is decided to be a member returns `Some(i)`
-/// with `i` being the number of iterations (up to `limit`)
-/// the approximation ran for.
+/// If `c` escapes, returns `Some(mu)` with `mu` the smoothed, fractional
+/// iteration count at which it did so (see `smooth_iteration_count`).
 /// A result of `None` indicates the approximation iterated to the limit
-fn approx_mandelbrot_test(c: Complex<f64>, limit: u32) -> Option<u32> {
+/// without escaping, i.e. `c` is considered to be in the set
+fn approx_mandelbrot_test(c: Complex<f64>, limit: u32, kind: FractalKind) -> Option<f64> {
     let mut z = Complex { re: 0.0, im: 0.0 }; // `re` is cartesian x, `im` cartesian y
     for i in 0..limit {
-        z = z * z + c; // `z` outside a ball of radius two, centered at the origin will grow to inf
+        z = fractal_step(kind, z, c); // `z` outside a ball of radius two, centered at the origin will grow to inf
         if z.norm_sqr() > 4.0 { // compare the squared distance, cheaper than using sqroot
-            return Some(i);
+            // a couple more iterations past escape sharpens the smoothed count
+            z = fractal_step(kind, z, c);
+            z = fractal_step(kind, z, c);
+            return Some(smooth_iteration_count(i, z));
         }
     }
     None
@@ -107,24 +196,289 @@ fn test_pixel_to_point() {
     assert_eq!(pixel_to_point((100, 100), (25, 75), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 }), Complex { re: -0.5, im: -0.5 });
 }
 
-/// Render pixel buffer
+/// Translate a point in the complex plane back to pixel coordinates
+/// Inverse of `pixel_to_point`; returns `None` when the point falls outside `bounds`
+fn point_to_pixel(bounds: (usize, usize),
+                   point: Complex<f64>,
+                   upper_left: Complex<f64>,
+                   lower_right: Complex<f64>) -> Option<(usize, usize)> {
+    let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
+
+    let col = (point.re - upper_left.re) / width * bounds.0 as f64;
+    let row = (upper_left.im - point.im) / height * bounds.1 as f64;
+
+    if col < 0.0 || row < 0.0 || col >= bounds.0 as f64 || row >= bounds.1 as f64 {
+        None
+    } else {
+        Some((col as usize, row as usize))
+    }
+}
+
+#[test]
+fn test_point_to_pixel() {
+    assert_eq!(point_to_pixel((100, 100), Complex { re: -0.5, im: -0.5 }, Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 }), Some((25, 75)));
+    assert_eq!(point_to_pixel((100, 100), Complex { re: -2.0, im: -0.5 }, Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 }), None);
+}
+
+/// Default escape-test iteration cap when the `iters` query parameter is absent
+const DEFAULT_ITERS: u32 = 255;
+
+/// Upper bound accepted for the `iters` query parameter, past which every
+/// non-escaping pixel would burn an unreasonable amount of CPU time
+const MAX_ITERS: u32 = 10_000;
+
+/// Upper bound accepted for the `ss` supersampling query parameter, past
+/// which the supersampled pixel buffer becomes an unreasonable allocation
+const MAX_SS: usize = 8;
+
+/// Upper bound on the number of pixels in the (possibly supersampled)
+/// render buffer, regardless of how `bounds` and `ss` individually combine
+const MAX_RENDER_PIXELS: usize = 64 * 1024 * 1024;
+
+/// Default number of random samples drawn by `/buddhabrot.png` when the
+/// `samples` query parameter is absent
+const DEFAULT_BUDDHABROT_SAMPLES: u32 = 1_000_000;
+
+/// Accumulate orbits into a Buddhabrot histogram
+///
+/// `samples` random points `c` are drawn from the `upper_left`/`lower_right`
+/// region. Orbits that escape before the iteration limit have every visited
+/// point mapped back to a pixel via `point_to_pixel` and counted in
+/// `histogram`; orbits that never escape are discarded entirely
+fn accumulate_buddhabrot(histogram: &mut [u32],
+                          bounds: (usize, usize),
+                          upper_left: Complex<f64>,
+                          lower_right: Complex<f64>,
+                          fractal: FractalKind,
+                          samples: u32,
+                          iters: u32) {
+    assert!(histogram.len() == bounds.0 * bounds.1);
+
+    let mut rng = rand::thread_rng();
+
+    for _ in 0 .. samples {
+        let c = Complex {
+            re: rng.gen_range(upper_left.re, lower_right.re),
+            im: rng.gen_range(lower_right.im, upper_left.im)
+        };
+
+        let mut z = Complex { re: 0.0, im: 0.0 };
+        let mut orbit = Vec::with_capacity(64);
+        let mut escaped = false;
+
+        for _ in 0 .. iters {
+            z = fractal_step(fractal, z, c);
+            orbit.push(z);
+            if z.norm_sqr() > 4.0 {
+                escaped = true;
+                break;
+            }
+        }
+
+        if escaped {
+            for point in orbit {
+                if let Some(pixel) = point_to_pixel(bounds, point, upper_left, lower_right) {
+                    histogram[pixel.1 * bounds.0 + pixel.0] += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Normalize a histogram to 8-bit intensities
+/// Scales by the maximum bin, then applies a square-root (gamma) curve so
+/// the rare, heavily-visited pixels don't wash out the sparser detail
+fn normalize_histogram(histogram: &[u32]) -> Vec<u8> {
+    let max = histogram.iter().cloned().max().unwrap_or(0);
+    if max == 0 {
+        return vec![0; histogram.len()];
+    }
+    histogram.iter().map(|&count| {
+        let scaled = (count as f64 / max as f64).sqrt();
+        (scaled * 255.0).round() as u8
+    }).collect()
+}
+
+#[test]
+fn test_normalize_histogram() {
+    assert_eq!(normalize_histogram(&[0, 0, 0]), vec![0, 0, 0]);
+    assert_eq!(normalize_histogram(&[0, 1, 4]), vec![0, 128, 255]);
+}
+
+/// RGB color output, selected by the `palette` query parameter
+///
+/// `Fire` and `Ocean` interpolate through a small list of anchor colors;
+/// `Hsv` sweeps the escape value across hue at full saturation and value
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Palette {
+    #[default]
+    Grayscale,
+    Fire,
+    Ocean,
+    Hsv,
+}
+
+impl FromStr for Palette {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grayscale" => Ok(Palette::Grayscale),
+            "fire" => Ok(Palette::Fire),
+            "ocean" => Ok(Palette::Ocean),
+            "hsv" => Ok(Palette::Hsv),
+            _ => Err(())
+        }
+    }
+}
+
+#[test]
+fn test_palette_from_str() {
+    assert_eq!(Palette::from_str("grayscale"), Ok(Palette::Grayscale));
+    assert_eq!(Palette::from_str("fire"), Ok(Palette::Fire));
+    assert_eq!(Palette::from_str("ocean"), Ok(Palette::Ocean));
+    assert_eq!(Palette::from_str("hsv"), Ok(Palette::Hsv));
+    assert_eq!(Palette::from_str("nonsense"), Err(()));
+}
+
+/// Convert an HSV color (`h` in `[0, 360)`, `s` and `v` in `[0, 1]`) to RGB bytes
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [u8; 3] {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+    let (r, g, b) = if h < 60.0 {
+        (c, x, 0.0)
+    } else if h < 120.0 {
+        (x, c, 0.0)
+    } else if h < 180.0 {
+        (0.0, c, x)
+    } else if h < 240.0 {
+        (0.0, x, c)
+    } else if h < 300.0 {
+        (x, 0.0, c)
+    } else {
+        (c, 0.0, x)
+    };
+    [
+        ((r + m) * 255.0).round() as u8,
+        ((g + m) * 255.0).round() as u8,
+        ((b + m) * 255.0).round() as u8
+    ]
+}
+
+#[test]
+fn test_hsv_to_rgb() {
+    assert_eq!(hsv_to_rgb(0.0, 1.0, 1.0), [255, 0, 0]);
+    assert_eq!(hsv_to_rgb(120.0, 1.0, 1.0), [0, 255, 0]);
+    assert_eq!(hsv_to_rgb(240.0, 1.0, 1.0), [0, 0, 255]);
+}
+
+/// Interpolate between two RGB colors by `t` in `[0, 1]`
+fn lerp_color(a: [u8; 3], b: [u8; 3], t: f64) -> [u8; 3] {
+    [
+        (a[0] as f64 + (b[0] as f64 - a[0] as f64) * t).round() as u8,
+        (a[1] as f64 + (b[1] as f64 - a[1] as f64) * t).round() as u8,
+        (a[2] as f64 + (b[2] as f64 - a[2] as f64) * t).round() as u8
+    ]
+}
+
+/// Interpolate through a list of anchor colors by `t` in `[0, 1]`
+fn gradient(anchors: &[[u8; 3]], t: f64) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let segments = anchors.len() - 1;
+    let scaled = t * segments as f64;
+    let index = (scaled as usize).min(segments - 1);
+    lerp_color(anchors[index], anchors[index + 1], scaled - index as f64)
+}
+
+const FIRE_ANCHORS: [[u8; 3]; 4] = [[0, 0, 0], [128, 0, 0], [255, 128, 0], [255, 255, 200]];
+const OCEAN_ANCHORS: [[u8; 3]; 4] = [[0, 0, 20], [0, 40, 100], [0, 120, 180], [200, 240, 255]];
+
+/// Map an escape-test result to an RGB color under `palette`
+/// Interior (non-escaping) points are always black
+fn color_for(escape: Option<f64>, limit: f64, palette: Palette) -> [u8; 3] {
+    match escape {
+        None => [0, 0, 0],
+        Some(mu) => {
+            let t = (mu / limit).clamp(0.0, 1.0);
+            match palette {
+                Palette::Grayscale => {
+                    let shade = (255.0 - t * 255.0).round() as u8;
+                    [shade, shade, shade]
+                }
+                Palette::Fire => gradient(&FIRE_ANCHORS, t),
+                Palette::Ocean => gradient(&OCEAN_ANCHORS, t),
+                Palette::Hsv => hsv_to_rgb(t * 360.0, 1.0, 1.0)
+            }
+        }
+    }
+}
+
+/// Render an RGB pixel buffer, 3 bytes per pixel
+/// `iters` caps the number of iterations the escape test runs before giving
+/// up on a point, i.e. deciding it belongs to the set
+///
+/// Palette coloring is normalized against `DEFAULT_ITERS` rather than `iters`
+/// itself: escape counts stay small regardless of how high the caller raises
+/// the cap, so normalizing against the (much larger) cap would wash the
+/// whole image out toward the "still escaping" end of every palette
 fn render(pixels: &mut [u8],
           bounds: (usize, usize),
           upper_left: Complex<f64>,
-          lower_right: Complex<f64>) {
-    assert!(pixels.len() == bounds.0 * bounds.1);
+          lower_right: Complex<f64>,
+          fractal: FractalKind,
+          palette: Palette,
+          iters: u32) {
+    assert!(pixels.len() == bounds.0 * bounds.1 * 3);
 
     for row in 0 .. bounds.1 {
         for col in 0 .. bounds.0 {
             let point = pixel_to_point(bounds, (col, row), upper_left, lower_right);
-            pixels[row * bounds.0 + col] = match approx_mandelbrot_test(point, 255) {
-                None => 0,
-                Some(n) => 255 - n as u8
-            };
+            let color = color_for(approx_mandelbrot_test(point, iters, fractal), DEFAULT_ITERS as f64, palette);
+            let offset = (row * bounds.0 + col) * 3;
+            pixels[offset .. offset + 3].copy_from_slice(&color);
         }
     }
 }
 
+/// Box-average an RGB buffer rendered at `ss`x the target resolution down to
+/// `bounds`, removing the aliasing along the set boundary that comes from
+/// sampling the fractal at only one point per output pixel
+fn downsample(supersampled: &[u8], bounds: (usize, usize), ss: usize) -> Vec<u8> {
+    let hi_width = bounds.0 * ss;
+    let mut pixels = vec![0u8; bounds.0 * bounds.1 * 3];
+
+    for row in 0 .. bounds.1 {
+        for col in 0 .. bounds.0 {
+            let mut sum = [0u32; 3];
+            for sub_row in 0 .. ss {
+                for sub_col in 0 .. ss {
+                    let hi_offset = ((row * ss + sub_row) * hi_width + (col * ss + sub_col)) * 3;
+                    sum[0] += supersampled[hi_offset] as u32;
+                    sum[1] += supersampled[hi_offset + 1] as u32;
+                    sum[2] += supersampled[hi_offset + 2] as u32;
+                }
+            }
+            let count = (ss * ss) as u32;
+            let offset = (row * bounds.0 + col) * 3;
+            pixels[offset] = (sum[0] / count) as u8;
+            pixels[offset + 1] = (sum[1] / count) as u8;
+            pixels[offset + 2] = (sum[2] / count) as u8;
+        }
+    }
+
+    pixels
+}
+
+#[test]
+fn test_downsample() {
+    let supersampled = vec![
+        0, 0, 0,     10, 20, 30,
+        20, 30, 40,  30, 40, 50,
+    ];
+    assert_eq!(downsample(&supersampled, (1, 1), 2), vec![15, 22, 30]);
+}
+
 fn get_index_page(_request: &mut Request) -> IronResult<Response> {
     let mut response = Response::new();
 
@@ -198,30 +552,244 @@ fn get_mandelbrot_image(request: &mut Request) -> IronResult<Response> {
         }
     };
 
-    let mut pixels = vec![0; bounds.0 * bounds.1];
+    let fractal = match query_data.get("fractal") {
+        None => FractalKind::default(),
+        Some(f) => match FractalKind::from_str(&f[0]) {
+            Err(_) => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted fractal\n"));
+                return Ok(response);
+            }
+            Ok(fractal) => fractal
+        }
+    };
+
+    let palette = match query_data.get("palette") {
+        None => Palette::default(),
+        Some(p) => match Palette::from_str(&p[0]) {
+            Err(_) => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted palette\n"));
+                return Ok(response);
+            }
+            Ok(palette) => palette
+        }
+    };
+
+    let iters = match query_data.get("iters") {
+        None => DEFAULT_ITERS,
+        Some(i) => match u32::from_str(&i[0]) {
+            Err(_) => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted iters\n"));
+                return Ok(response);
+            }
+            Ok(iters) if iters < 1 || iters > MAX_ITERS => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted iters\n"));
+                return Ok(response);
+            }
+            Ok(iters) => iters
+        }
+    };
 
+    let ss = match query_data.get("ss") {
+        None => 1,
+        Some(s) => match usize::from_str(&s[0]) {
+            Err(_) => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted ss\n"));
+                return Ok(response);
+            }
+            Ok(ss) if ss < 1 || ss > MAX_SS => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted ss\n"));
+                return Ok(response);
+            }
+            Ok(ss) => ss
+        }
+    };
+
+    if bounds.0 * ss * (bounds.1 * ss) > MAX_RENDER_PIXELS {
+        response.set_mut(status::BadRequest);
+        response.set_mut(format!("requested resolution is too large\n"));
+        return Ok(response);
+    }
+
+    let render_bounds = (bounds.0 * ss, bounds.1 * ss);
+    let mut pixels = vec![0; render_bounds.0 * render_bounds.1 * 3];
+
+    pixels.par_chunks_mut(render_bounds.0 * 3).enumerate().for_each(|(row, band)| {
+        let band_bounds = (render_bounds.0, 1);
+        let band_upper_left = pixel_to_point(render_bounds, (0, row), upper_left, lower_right);
+        let band_lower_right = pixel_to_point(render_bounds, (render_bounds.0, row + 1), upper_left, lower_right);
+
+        render(band, band_bounds, band_upper_left, band_lower_right, fractal, palette, iters);
+    });
+
+    let pixels = if ss > 1 {
+        downsample(&pixels, bounds, ss)
+    } else {
+        pixels
+    };
+
+    let mut buf: Vec<u8> = Vec::new();
+
+    // create a new lexical scope so the mutable borrow handed to the encoder will be dropped
+    // we send pass buf to set_mut
     {
-        let threads = 8;
-        let rows_per_band = bounds.1 / threads + 1;
+        let encoder = PNGEncoder::new(&mut buf);
+
+        match encoder.encode(&pixels, bounds.0 as u32, bounds.1 as u32, ColorType::RGB(8)) {
+            Err(e) => {
+                response.set_mut(status::InternalServerError);
+                response.set_mut(format!("failed to encode png: {:?}\n", e));
+                return Ok(response);
+            }
+            Ok(_) => ()
+        }
+    }
 
-        // this new scope hides this mutable borrow, so that write_image typechecks
-        let bands: Vec<&mut [u8]> = pixels.chunks_mut(rows_per_band * bounds.0).collect();
+    response.set_mut(status::Ok);
+    response.set_mut(mime!(Image/Png));
+    response.set_mut(buf);
+    Ok(response)
+}
 
-        crossbeam::scope(|spawner| {
-            for (i, band) in bands.into_iter().enumerate() {
-                let top = rows_per_band * i;
-                let height = band.len() / bounds.0;
-                let band_bounds = (bounds.0, height);
-                let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-                let band_lower_right = pixel_to_point(bounds, (bounds.0, top + height), upper_left, lower_right);
+fn get_buddhabrot_image(request: &mut Request) -> IronResult<Response> {
+    let mut response = Response::new();
 
-                spawner.spawn(move || {
-                    render(band, band_bounds, band_upper_left, band_lower_right);
-                });
+    let query_data = match request.get_ref::<UrlEncodedQuery>() {
+        Err(e) => {
+            response.set_mut(status::BadRequest);
+            response.set_mut(format!("Error parsing query string: {:?}\n", e));
+            return Ok(response);
+        }
+        Ok(map) => map
+    };
+
+    let bounds = match query_data.get("b") {
+        None => {
+            response.set_mut(status::BadRequest);
+            response.set_mut(format!("missing the 'b' parameter\n"));
+            return Ok(response);
+        }
+        Some(b) => match parse_pair(&b[0], 'x') {
+            None => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted bounds\n"));
+                return Ok(response);
             }
+            Some(bounds) => bounds
+        }
+    };
+
+    let lower_right = match query_data.get("l") {
+        None => {
+            response.set_mut(status::BadRequest);
+            response.set_mut(format!("missing the 'l' parameter\n"));
+            return Ok(response);
+        }
+        Some(l) => match parse_complex(&l[0]) {
+            None => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted lower right\n"));
+                return Ok(response);
+            }
+            Some(lower_right) => lower_right
+        }
+    };
+
+    let upper_left = match query_data.get("u") {
+        None => {
+            response.set_mut(status::BadRequest);
+            response.set_mut(format!("missing the 'u' parameter\n"));
+            return Ok(response);
+        }
+        Some(u) => match parse_complex(&u[0]) {
+            None => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted upper left\n"));
+                return Ok(response);
+            }
+            Some(upper_left) => upper_left
+        }
+    };
+
+    if lower_right.re <= upper_left.re || upper_left.im <= lower_right.im {
+        response.set_mut(status::BadRequest);
+        response.set_mut(format!("degenerate or inverted region\n"));
+        return Ok(response);
+    }
+
+    let fractal = match query_data.get("fractal") {
+        None => FractalKind::default(),
+        Some(f) => match FractalKind::from_str(&f[0]) {
+            Err(_) => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted fractal\n"));
+                return Ok(response);
+            }
+            Ok(fractal) => fractal
+        }
+    };
+
+    let samples = match query_data.get("samples") {
+        None => DEFAULT_BUDDHABROT_SAMPLES,
+        Some(s) => match u32::from_str(&s[0]) {
+            Err(_) => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted samples\n"));
+                return Ok(response);
+            }
+            Ok(samples) => samples
+        }
+    };
+
+    let iters = match query_data.get("iters") {
+        None => DEFAULT_ITERS,
+        Some(i) => match u32::from_str(&i[0]) {
+            Err(_) => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted iters\n"));
+                return Ok(response);
+            }
+            Ok(iters) if iters < 1 || iters > MAX_ITERS => {
+                response.set_mut(status::BadRequest);
+                response.set_mut(format!("misformatted iters\n"));
+                return Ok(response);
+            }
+            Ok(iters) => iters
+        }
+    };
+
+    let mut histogram = vec![0u32; bounds.0 * bounds.1];
+
+    {
+        let threads = 8;
+        let samples_per_thread = samples / threads as u32 + 1;
+
+        let partials: Vec<Vec<u32>> = crossbeam::scope(|spawner| {
+            let handles: Vec<_> = (0 .. threads).map(|_| {
+                spawner.spawn(move || {
+                    let mut partial = vec![0u32; bounds.0 * bounds.1];
+                    accumulate_buddhabrot(&mut partial, bounds, upper_left, lower_right, fractal, samples_per_thread, iters);
+                    partial
+                })
+            }).collect();
+
+            handles.into_iter().map(|handle| handle.join()).collect()
         });
+
+        for partial in partials {
+            for (total, count) in histogram.iter_mut().zip(partial.into_iter()) {
+                *total += count;
+            }
+        }
     }
 
+    let pixels = normalize_histogram(&histogram);
+
     let mut buf: Vec<u8> = Vec::new();
 
     // create a new lexical scope so the mutable borrow handed to the encoder will be dropped
@@ -254,6 +822,7 @@ fn main() {
 
     router.get("/", get_index_page, "root");
     router.get("/mandelbrot.png", get_mandelbrot_image, "mandelbrot");
+    router.get("/buddhabrot.png", get_buddhabrot_image, "buddhabrot");
 
     let mut chain = Chain::new(router);
 